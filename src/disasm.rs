@@ -0,0 +1,60 @@
+// Listing mode: decode the final image one 2-byte instruction at a time,
+// annotating each line with the symbol that starts there (if any) and the
+// target of any relocation patched into that word, mirroring the
+// "write object address and size in asm comments" style of decomp tooling.
+
+use std::collections::HashMap;
+
+use crate::{ADDI_R_AC, CLEAR_R_AC, HLT, INSTRUCTION_BYTES, JMP_R_AC, SHIFT_R_AC};
+
+fn mnemonic(hi: u8, lo: u8) -> String {
+    let word = ((hi as u16) << 8) | lo as u16;
+
+    if word == CLEAR_R_AC {
+        String::from("CLEAR R_AC")
+    } else if word == SHIFT_R_AC {
+        String::from("SHIFT R_AC, #8")
+    } else if word == JMP_R_AC {
+        String::from("JMP R_AC")
+    } else if hi == ADDI_R_AC {
+        format!("ADDI R_AC, #{:#04x}", lo)
+    } else if hi == HLT {
+        String::from("HLT")
+    } else {
+        String::from("???")
+    }
+}
+
+// `symbols` maps a symbol name to its byte offset in `image`; `relocations`
+// maps a patched byte offset in `image` to the name of the symbol it was
+// patched to reference.
+pub(crate) fn disassemble(
+    image: &[u8],
+    symbols: &HashMap<usize, String>,
+    relocations: &HashMap<usize, String>,
+) -> String {
+    let mut out = String::new();
+    let mut offset = 0;
+    let mut word = 0;
+
+    while offset + INSTRUCTION_BYTES <= image.len() {
+        let hi = image[offset];
+        let lo = image[offset + 1];
+
+        out.push_str(&format!("{:6}  {:02x}{:02x}  {}", word, hi, lo, mnemonic(hi, lo)));
+
+        if let Some(name) = symbols.get(&offset) {
+            out.push_str(&format!("  ; <{}>", name));
+        }
+        if let Some(name) = relocations.get(&offset) {
+            out.push_str(&format!("  ; reloc -> {}", name));
+        }
+
+        out.push('\n');
+
+        offset += INSTRUCTION_BYTES;
+        word += 1;
+    }
+
+    out
+}
@@ -0,0 +1,227 @@
+// Reachability-based dead-code elimination, analogous to FORCEACTIVE /
+// FORCEFILES in decomp linker scripts: starting from the entry symbol (plus
+// any force-active names given on the command line), walk the reference
+// graph formed by relocations and keep only the code that's reachable.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::{SimpleSymbol, WriteSymbol};
+
+// A contiguous run of bytes that survived garbage collection, recording
+// where it used to live in the pre-GC `executable` buffer and where it was
+// copied to in the compacted one.
+pub(crate) struct Block {
+    old_start: usize,
+    old_end: usize,
+    new_start: usize,
+}
+
+fn containing_symbol(simple_symbols: &[SimpleSymbol], offset: usize) -> Option<usize> {
+    simple_symbols.iter()
+        .filter(|s| s.name.is_some() && s.size > 0)
+        .find(|s| offset >= s.exec_address && offset < s.exec_address + s.size)
+        .map(|s| s.symbol_index)
+}
+
+pub(crate) fn reachable_symbols(
+    simple_symbols: &[SimpleSymbol],
+    write_what_where: &[WriteSymbol],
+    entry_index: usize,
+    force_active: &[usize],
+) -> HashSet<usize> {
+    // A relocation names its target by index in the referencing file, which
+    // for a cross-file reference is that file's undefined entry; resolve it
+    // by name to the defining (nonzero-size) symbol.
+    let resolve = |idx: usize| -> usize {
+        match &simple_symbols[idx].name {
+            Some(name) => simple_symbols.iter()
+                .find(|s| s.name.as_ref() == Some(name) && s.size > 0)
+                .map(|s| s.symbol_index)
+                .unwrap_or(idx),
+            None => idx
+        }
+    };
+
+    let mut edges: HashMap<usize, Vec<usize>> = HashMap::new();
+    for www in write_what_where {
+        if let Some(src) = containing_symbol(simple_symbols, www.cs_offset) {
+            edges.entry(src).or_insert_with(Vec::new).push(resolve(www.symbol_index));
+        }
+    }
+
+    let mut reachable = HashSet::new();
+    let mut worklist = VecDeque::new();
+    worklist.push_back(resolve(entry_index));
+    worklist.extend(force_active.iter().map(|&idx| resolve(idx)));
+
+    while let Some(idx) = worklist.pop_front() {
+        if !reachable.insert(idx) {
+            continue;
+        }
+        if let Some(targets) = edges.get(&idx) {
+            for &t in targets {
+                if !reachable.contains(&t) {
+                    worklist.push_back(t);
+                }
+            }
+        }
+    }
+
+    reachable
+}
+
+// Rebuild `executable` containing only reachable symbols' code, in their
+// original layout order, and return the blocks needed to remap addresses
+// and relocation sites against the compacted buffer. `align` (from
+// SimpleSymbol.align) is a per-*file* ALIGN(n), stamped onto every symbol
+// that file contributed -- so it's only re-applied against `base` once, at
+// the first surviving span of a run of spans from the same object file,
+// the same way main's initial layout pass pads once per file rather than
+// once per symbol. Re-padding every span would insert bytes between
+// symbols that used to be contiguous, breaking fall-through between them.
+pub(crate) fn compact(
+    simple_symbols: &[SimpleSymbol],
+    executable: &[u8],
+    reachable: &HashSet<usize>,
+    base: usize,
+) -> (Vec<u8>, Vec<Block>) {
+    let mut spans: Vec<(usize, usize, usize, &str)> = simple_symbols.iter()
+        .filter(|s| s.name.is_some() && s.size > 0 && reachable.contains(&s.symbol_index))
+        .map(|s| (s.exec_address, s.exec_address + s.size, s.align, s.object_file.as_str()))
+        .collect();
+    spans.sort();
+    spans.dedup();
+
+    let mut new_executable = Vec::new();
+    let mut blocks = Vec::new();
+    let mut last_object_file = None;
+
+    for (old_start, old_end, align, object_file) in spans {
+        if align > 1 && last_object_file != Some(object_file) {
+            let target = base + new_executable.len();
+            let padding = (align - (target % align)) % align;
+            new_executable.extend(std::iter::repeat(0u8).take(padding));
+        }
+        last_object_file = Some(object_file);
+
+        let new_start = new_executable.len();
+        new_executable.extend_from_slice(&executable[old_start..old_end]);
+        blocks.push(Block { old_start, old_end, new_start });
+    }
+
+    (new_executable, blocks)
+}
+
+// Map an offset into the pre-GC `executable` buffer to its offset in the
+// compacted one, or None if it fell inside code that got collected.
+pub(crate) fn remap(blocks: &[Block], old_offset: usize) -> Option<usize> {
+    // A real (nonzero-size) symbol always gets its own Block, so prefer an
+    // exact interior match first: at a seam where one block's old_end
+    // equals the next block's old_start, this picks the block that starts
+    // here rather than the one that merely ends here, even if the next
+    // block gained alignment padding during compaction.
+    if let Some(b) = blocks.iter().find(|b| old_offset >= b.old_start && old_offset < b.old_end) {
+        return Some(b.new_start + (old_offset - b.old_start));
+    }
+
+    // Zero-size named symbols (e.g. end-of-section markers) are filtered
+    // out of `spans` in `compact` and so never get their own Block. Resolve
+    // one to the address immediately following its enclosing block.
+    blocks.iter()
+        .find(|b| old_offset == b.old_end)
+        .map(|b| b.new_start + (old_offset - b.old_start))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sym(name: &str, symbol_index: usize, exec_address: usize, size: usize, align: usize, object_file: &str) -> SimpleSymbol {
+        SimpleSymbol {
+            name: Some(String::from(name)),
+            symbol_index,
+            address: exec_address,
+            size,
+            align,
+            exec_address,
+            object_file: String::from(object_file)
+        }
+    }
+
+    #[test]
+    fn compact_drops_unreachable_and_reapplies_align() {
+        // dead sits between live_a and live_b; live_b is its own file with
+        // ALIGN(4) and isn't naturally aligned once dead is dropped, so
+        // compact must re-pad it.
+        let symbols = vec![
+            sym("live_a", 0, 0, 2, 1, "a.o"),
+            sym("dead", 1, 2, 2, 1, "a.o"),
+            sym("live_b", 2, 4, 2, 4, "b.o"),
+        ];
+        let executable = vec![0xAA, 0xAA, 0xDD, 0xDD, 0xBB, 0xBB];
+        let reachable: HashSet<usize> = [0, 2].iter().cloned().collect();
+
+        let (new_executable, blocks) = compact(&symbols, &executable, &reachable, 0);
+
+        assert_eq!(new_executable, vec![0xAA, 0xAA, 0, 0, 0xBB, 0xBB]);
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].new_start, 0);
+        assert_eq!(blocks[1].new_start, 4);
+    }
+
+    #[test]
+    fn compact_pads_once_per_file_not_per_symbol() {
+        // Two symbols from the same ALIGN(4) file, both reachable and
+        // contiguous: only the first one should trigger padding. A second
+        // pad before "second" would insert bytes between code that was
+        // contiguous in the plain link, breaking fall-through between them.
+        let symbols = vec![
+            sym("first", 0, 0, 2, 4, "lib.o"),
+            sym("second", 1, 2, 2, 4, "lib.o"),
+        ];
+        let executable = vec![0xAA, 0xAA, 0xBB, 0xBB];
+        let reachable: HashSet<usize> = [0, 1].iter().cloned().collect();
+
+        let (new_executable, blocks) = compact(&symbols, &executable, &reachable, 0);
+
+        assert_eq!(new_executable, vec![0xAA, 0xAA, 0xBB, 0xBB]);
+        assert_eq!(blocks[0].new_start, 0);
+        assert_eq!(blocks[1].new_start, 2);
+    }
+
+    #[test]
+    fn remap_translates_interior_offsets_and_drops_collected_ones() {
+        let blocks = vec![
+            Block { old_start: 0, old_end: 2, new_start: 0 },
+            Block { old_start: 4, old_end: 6, new_start: 4 },
+        ];
+
+        assert_eq!(remap(&blocks, 1), Some(1));
+        assert_eq!(remap(&blocks, 5), Some(5));
+        assert_eq!(remap(&blocks, 3), None);
+    }
+
+    #[test]
+    fn remap_resolves_zero_size_symbol_at_block_end() {
+        // A zero-size named symbol marking the end of a block (e.g. an
+        // end-of-section marker) sits exactly at old_end and isn't its own
+        // span, but a relocation against it should still resolve.
+        let blocks = vec![Block { old_start: 0, old_end: 4, new_start: 10 }];
+
+        assert_eq!(remap(&blocks, 4), Some(14));
+    }
+
+    #[test]
+    fn remap_prefers_next_block_at_a_padded_seam() {
+        // "a" and "b" were contiguous pre-GC (a's old_end == b's old_start),
+        // but b picked up 2 bytes of alignment padding during compaction.
+        // The seam offset must resolve to b's padded new_start, not to the
+        // byte right after a's unpadded new_end.
+        let blocks = vec![
+            Block { old_start: 0, old_end: 2, new_start: 0 },
+            Block { old_start: 2, old_end: 4, new_start: 4 },
+        ];
+
+        assert_eq!(remap(&blocks, 2), Some(4));
+    }
+}
@@ -1,6 +1,12 @@
 extern crate object;
 extern crate byteorder;
 
+mod script;
+mod gc;
+mod mapfile;
+mod disasm;
+mod vm;
+
 use std::io::prelude::*;
 use std::io::Cursor;
 use std::collections::HashMap;
@@ -13,18 +19,14 @@ const SHIFT_R_AC: u16 = 0b0011110100000000;
 const JMP_R_AC: u16 = 0b1011110000000000;
 const ADDI_R_AC: u8 = 0b00011100;
 const HLT: u8 = 0b11111111;
-const ENTRY: &str = "main";
+pub(crate) const ENTRY: &str = "main";
 const ENTRYPOINT_LEN: usize = 10;
 const INSTRUCTION_BYTES: usize = 2;
 
-fn load_addr_code(addr: usize) -> Vec<u8> {
-    if addr > std::u16::MAX as usize {
-        panic!("Address too big");
-    }
-
-    let top = (addr >> 8) as u8;
-    let bottom = (addr & 0xFF) as u8;
-
+// Clear R_AC, ADDI the high byte, shift left 8, ADDI the low byte. `top`
+// and `bottom` are taken as raw bit patterns, so callers can load either an
+// unsigned word index or the two's-complement bits of a signed offset.
+fn addr_load_sequence(top: u8, bottom: u8) -> Vec<u8> {
     let mut v = Vec::<u8>::new();
 
     // Clear out R_AC
@@ -46,6 +48,22 @@ fn load_addr_code(addr: usize) -> Vec<u8> {
     return v;
 }
 
+fn load_addr_code(addr: usize) -> Vec<u8> {
+    if addr > std::u16::MAX as usize {
+        panic!("Address too big");
+    }
+
+    addr_load_sequence((addr >> 8) as u8, (addr & 0xFF) as u8)
+}
+
+// Convert a linked address (a value as stored in `symbol_addresses`, i.e.
+// `base` plus an offset into `executable`) into the word index that R_AC
+// must hold to jump there. JMP_R_AC lands the PC one word index early, so
+// every caller that lands on a target via JMP_R_AC subtracts one here.
+fn word_index(linked_address: usize, entrypoint_len: usize) -> usize {
+    ((linked_address + entrypoint_len) / INSTRUCTION_BYTES).wrapping_sub(1)
+}
+
 fn gen_entrypoint(addr: usize) -> Vec<u8> {
     let mut ac = load_addr_code(addr);
     ac.push((JMP_R_AC >> 8) as u8);
@@ -84,22 +102,118 @@ struct SimpleSymbol {
     name: Option<String>,
     symbol_index: usize,
     address: usize,
-    size: usize
+    size: usize,
+    align: usize,
+    // Offset of this symbol within the (pre-GC) `executable` buffer, i.e.
+    // not counting `base` or the synthesized entrypoint.
+    exec_address: usize,
+    object_file: String
+}
+
+// The relocation `typ` byte: how a write-what-where site gets patched once
+// the target symbol's final address is known.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RelocType {
+    // Absolute symbol address loaded into R_AC via the full 8-byte sequence.
+    Absolute,
+    // PC-relative site (type 1). Rejected with an error when patching: no
+    // signed displacement is loaded, since JMP_R_AC only jumps to an
+    // absolute word index.
+    PcRelative,
+    // Patch only the high byte of an existing ADDI_R_AC immediate.
+    HiByte,
+    // Patch only the low byte of an existing ADDI_R_AC immediate.
+    LoByte
+}
+
+impl RelocType {
+    fn from_byte(b: u8) -> Result<RelocType, String> {
+        match b {
+            0 => Ok(RelocType::Absolute),
+            1 => Ok(RelocType::PcRelative),
+            2 => Ok(RelocType::HiByte),
+            3 => Ok(RelocType::LoByte),
+            other => Err(format!("Unknown relocation type: {}", other))
+        }
+    }
 }
 
 #[derive(Debug)]
 struct WriteSymbol {
     symbol_index: usize,
-    cs_offset: usize
+    cs_offset: usize,
+    typ: RelocType
+}
+
+fn parse_args() -> Result<(Option<String>, Vec<String>, Vec<String>, Option<String>, bool, bool, bool), String> {
+    let mut script_path = None;
+    let mut file_args = Vec::new();
+    let mut keep = Vec::new();
+    let mut map_path = None;
+    let mut disassemble = false;
+    let mut run = false;
+    let mut gc_sections = false;
+    let mut args = env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        if arg == "--script" {
+            script_path = Some(args.next().ok_or_else(|| String::from("--script requires a path"))?);
+        } else if arg == "--keep" {
+            keep.push(args.next().ok_or_else(|| String::from("--keep requires a symbol name"))?);
+        } else if arg == "--map" {
+            map_path = Some(args.next().ok_or_else(|| String::from("--map requires a path"))?);
+        } else if arg == "--disassemble" {
+            disassemble = true;
+        } else if arg == "--run" {
+            run = true;
+        } else if arg == "--gc-sections" {
+            gc_sections = true;
+        } else {
+            file_args.push(arg);
+        }
+    }
+
+    Ok((script_path, file_args, keep, map_path, disassemble, run, gc_sections))
 }
 
 fn main() {
     let arg_len = env::args().len();
     if arg_len <= 1 {
-        eprintln!("Usage: {} <file> ...", env::args().next().unwrap());
+        eprintln!("Usage: {} [--script <linker-script>] [--gc-sections] [--keep <symbol>]... [--map <path>] [--disassemble] [--run] <file> ...", env::args().next().unwrap());
         process::exit(1);
     }
 
+    let (script_path, file_args, keep, map_path, disassemble, run, gc_sections) = match parse_args() {
+        Ok(v) => v,
+        Err(err) => {
+            println!("{}", err);
+            return;
+        }
+    };
+
+    let (base, entry, file_order) = match script_path {
+        Some(path) => {
+            let text = match fs::read_to_string(&path) {
+                Ok(text) => text,
+                Err(err) => {
+                    println!("Failed to open linker script '{}': {}", path, err);
+                    return;
+                }
+            };
+
+            let parsed = match script::parse(&text) {
+                Ok(parsed) => parsed,
+                Err(err) => {
+                    println!("Failed to parse linker script '{}': {}", path, err);
+                    return;
+                }
+            };
+
+            (parsed.base, parsed.entry, parsed.contributions.into_iter().map(|c| (c.path, c.align)).collect::<Vec<_>>())
+        },
+        None => (0, String::from(ENTRY), file_args.into_iter().map(|path| (path, 1)).collect())
+    };
+
     // let mut executable = Vec::new();
     let mut write_what_where = Vec::new();
     let mut simple_symbols = Vec::new();
@@ -107,7 +221,7 @@ fn main() {
     let mut symbol_addresses = HashMap::new();
     let mut executable = Vec::<u8>::new();
 
-    for file_path in env::args().skip(1) {
+    for (file_path, align) in file_order {
         // object::File has the same lifetime as the data passed to parse, so
         // this buffer needs to live
         let mut buffer = Vec::new();
@@ -116,6 +230,14 @@ fn main() {
             None => return
         };
 
+        // Pad up to the contribution's requested alignment before laying
+        // down its code, same as ALIGN(n) in a GC decomp linker script.
+        if align > 1 {
+            let target = base + executable.len();
+            let padding = (align - (target % align)) % align;
+            executable.extend(std::iter::repeat(0u8).take(padding));
+        }
+
         let symbol_offset = simple_symbols.len();
         let exec_offset = executable.len();
         let mut main_last_instruction_offset: Option<usize> = None;
@@ -125,7 +247,10 @@ fn main() {
                 name: None,
                 symbol_index: simple_symbols.len(),
                 address: symbol.address() as usize,
-                size: symbol.size() as usize
+                size: symbol.size() as usize,
+                align,
+                exec_address: exec_offset + symbol.address() as usize,
+                object_file: file_path.clone()
             };
 
             match symbol.name() {
@@ -139,9 +264,9 @@ fn main() {
                                     println!("Duplicate symbol: {}", *name);
                                     return;
                                 },
-                                None => { 
-                                    symbol_addresses.insert(String::from(*name), exec_offset + ss.address);
-                                    if *name == ENTRY {
+                                None => {
+                                    symbol_addresses.insert(String::from(*name), base + exec_offset + ss.address);
+                                    if *name == entry {
                                         main_last_instruction_offset = Some(exec_offset + ss.address + ss.size- 2);
                                     }
                                 }
@@ -190,21 +315,85 @@ fn main() {
 
         while (rdr.position() as usize) < relocations.len() {
             let cs_offset = rdr.read_u32::<LittleEndian>().unwrap() as usize;
-            let _typ = rdr.read_u8().unwrap();
+            let typ = match RelocType::from_byte(rdr.read_u8().unwrap()) {
+                Ok(typ) => typ,
+                Err(err) => {
+                    println!("{}", err);
+                    return;
+                }
+            };
             let symbol_id = rdr.read_u8().unwrap() as usize;
             let _pad = rdr.read_u16::<LittleEndian>().unwrap();
             write_what_where.push(WriteSymbol {
                 cs_offset: exec_offset + cs_offset,
-                symbol_index: symbol_offset + symbol_id
+                symbol_index: symbol_offset + symbol_id,
+                typ
             });
         }
     }
 
-    // Insert the jump to main
-    let entry_address = match symbol_addresses.get(ENTRY) {
-        Some(address) => ((*address + ENTRYPOINT_LEN) / INSTRUCTION_BYTES).wrapping_sub(1),
+    if !simple_symbols.iter().any(|s| s.name.as_deref() == Some(entry.as_str())) {
+        println!("Missing entry function: {}", entry);
+        return;
+    }
+
+    // Garbage-collect any code unreachable from the entry symbol, keeping
+    // anything named on the --keep (force-active) list alive regardless.
+    // Opt-in via --gc-sections: reachability is relocation-only, so code
+    // reached purely by fall-through (no relocation edge) would otherwise
+    // silently vanish from a plain link.
+    let (mut executable, symbol_addresses, write_what_where) = if gc_sections {
+        let entry_index = simple_symbols.iter()
+            .find(|s| s.name.as_deref() == Some(entry.as_str()))
+            .unwrap()
+            .symbol_index;
+
+        let mut force_active = Vec::new();
+        for name in &keep {
+            match simple_symbols.iter().find(|s| s.name.as_deref() == Some(name.as_str())) {
+                Some(s) => force_active.push(s.symbol_index),
+                None => {
+                    println!("Missing --keep symbol: {}", name);
+                    return;
+                }
+            }
+        }
+
+        let reachable = gc::reachable_symbols(&simple_symbols, &write_what_where, entry_index, &force_active);
+        let (new_executable, blocks) = gc::compact(&simple_symbols, &executable, &reachable, base);
+
+        let mut new_symbol_addresses = HashMap::new();
+        for s in &simple_symbols {
+            if let Some(name) = &s.name {
+                // Only the defining entry holds the symbol's address; an
+                // undefined reference of the same name must not overwrite it.
+                if symbol_addresses.get(name) != Some(&(base + s.exec_address)) {
+                    continue;
+                }
+                if let Some(new_offset) = gc::remap(&blocks, s.exec_address) {
+                    new_symbol_addresses.insert(name.clone(), base + new_offset);
+                }
+            }
+        }
+
+        let new_write_what_where: Vec<WriteSymbol> = write_what_where.iter().filter_map(|www| {
+            gc::remap(&blocks, www.cs_offset).map(|cs_offset| WriteSymbol {
+                cs_offset,
+                symbol_index: www.symbol_index,
+                typ: www.typ
+            })
+        }).collect();
+
+        (new_executable, new_symbol_addresses, new_write_what_where)
+    } else {
+        (executable, symbol_addresses, write_what_where)
+    };
+
+    // Insert the jump to the entry symbol
+    let entry_address = match symbol_addresses.get(&entry) {
+        Some(address) => word_index(*address, ENTRYPOINT_LEN),
         None => {
-            println!("Missing entry function: {}", ENTRY);
+            println!("Missing entry function: {}", entry);
             return;
         }
     };
@@ -212,23 +401,114 @@ fn main() {
     let entrypoint_jmp_code = gen_entrypoint(entry_address);
     assert!(entrypoint_jmp_code.len() == ENTRYPOINT_LEN);
 
+    // Byte offset in the final image (entrypoint + executable) -> name of
+    // the symbol each relocation site was patched to reference. Only used
+    // for the --disassemble listing.
+    let mut patched_names: HashMap<usize, String> = HashMap::new();
+
     for www in &write_what_where {
         let i = www.symbol_index;
         let s = &simple_symbols[i];
         let name = s.name.as_ref().unwrap();
+        patched_names.insert(www.cs_offset + entrypoint_jmp_code.len(), name.clone());
         let symbol_address = match symbol_addresses.get(name) {
-            Some(address) => ((*address + entrypoint_jmp_code.len()) / INSTRUCTION_BYTES).wrapping_sub(1),
+            Some(address) => word_index(*address, entrypoint_jmp_code.len()),
             None => {
                 println!("Missing symbol: {}", name);
                 return;
             }
         };
 
-        let insert_code = load_addr_code(symbol_address);
-        executable.splice(www.cs_offset..(www.cs_offset + insert_code.len()), insert_code.iter().cloned());
+        match www.typ {
+            RelocType::Absolute => {
+                let insert_code = load_addr_code(symbol_address);
+                executable.splice(www.cs_offset..(www.cs_offset + insert_code.len()), insert_code.iter().cloned());
+            },
+            RelocType::PcRelative => {
+                // JMP_R_AC always sets pc = r_ac absolutely (see vm.rs); there's
+                // no relative-jump primitive in this ISA for a loaded signed
+                // displacement to feed. The JMP_R_AC following a relocation
+                // site comes from the assembler that produced the object
+                // file, outside this linker's control, so there's no way to
+                // patch a PC-relative site that will actually jump correctly.
+                // Refuse instead of silently mis-patching it as an absolute
+                // address.
+                println!("PC-relative relocations are not supported by this ISA (symbol '{}')", name);
+                return;
+            },
+            RelocType::HiByte | RelocType::LoByte => {
+                let immediate = match www.typ {
+                    RelocType::HiByte => (symbol_address >> 8) as u8,
+                    _ => (symbol_address & 0xFF) as u8
+                };
+                executable[www.cs_offset] = ADDI_R_AC;
+                executable[www.cs_offset + 1] = immediate;
+            }
+        }
     }
 
     let mut outbuf = fs::File::create("/tmp/henlo.bin").unwrap();
     outbuf.write_all(&entrypoint_jmp_code).unwrap();
     outbuf.write_all(&executable).unwrap();
+
+    if let Some(map_path) = map_path {
+        match mapfile::write(&map_path, &simple_symbols, &symbol_addresses, &entry, base, entrypoint_jmp_code.len()) {
+            Ok(()) => (),
+            Err(err) => println!("Failed to write map file '{}': {}", map_path, err)
+        }
+    }
+
+    if disassemble {
+        let mut image = entrypoint_jmp_code.clone();
+        image.extend_from_slice(&executable);
+
+        let mut symbol_names = HashMap::new();
+        for (name, address) in &symbol_addresses {
+            symbol_names.insert(address - base + entrypoint_jmp_code.len(), name.clone());
+        }
+
+        print!("{}", disasm::disassemble(&image, &symbol_names, &patched_names));
+    }
+
+    if run {
+        let mut image = entrypoint_jmp_code.clone();
+        image.extend_from_slice(&executable);
+
+        match vm::run(&image) {
+            Ok(halt) => {
+                println!("HLT at word {} (R_AC = {:#06x})", halt.pc, halt.r_ac);
+                process::exit(0);
+            },
+            Err(vm::Trap::OutOfBounds(word)) => {
+                println!("Trap: execution ran past the end of the image at word {}", word);
+                process::exit(1);
+            },
+            Err(vm::Trap::UnknownOpcode(word, hi, lo)) => {
+                println!("Trap: unrecognized opcode {:02x}{:02x} at word {}", hi, lo, word);
+                process::exit(1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn word_index_lands_one_word_early() {
+        // entrypoint_len 0: address 0 is word 0, so JMP_R_AC (which lands
+        // one word early) needs word index usize::MAX's wrap, i.e. -1.
+        assert_eq!(word_index(0, 0), usize::MAX);
+        assert_eq!(word_index(2, 0), 0);
+        assert_eq!(word_index(4, 0), 1);
+    }
+
+    #[test]
+    fn word_index_accounts_for_entrypoint_length() {
+        // A 10-byte entrypoint occupies words 0..4; address 0 of the
+        // following executable buffer is word 5, so JMP_R_AC needs word 4.
+        assert_eq!(word_index(0, 10), 4);
+        assert_eq!(word_index(2, 10), 5);
+    }
 }
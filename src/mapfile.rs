@@ -0,0 +1,58 @@
+// Writes a `.map` file listing where every symbol ended up in the final
+// image: name, originating object file, final byte address, final word
+// index (what JMP_R_AC actually lands on), and size. Handy for correlating
+// emulator crashes back to source symbols.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+
+use crate::SimpleSymbol;
+
+pub(crate) fn write(
+    path: &str,
+    simple_symbols: &[SimpleSymbol],
+    symbol_addresses: &HashMap<String, usize>,
+    entry: &str,
+    base: usize,
+    entrypoint_len: usize,
+) -> std::io::Result<()> {
+    let mut rows: Vec<(usize, usize, usize, String, String)> = Vec::new();
+
+    // The synthesized entrypoint jump sequence always leads the image.
+    rows.push((base, 0, entrypoint_len, String::from("<entrypoint>"), String::from("(synthesized)")));
+
+    for s in simple_symbols {
+        let name = match &s.name {
+            Some(name) => name,
+            None => continue
+        };
+
+        // Symbols dropped by the GC pass have no final address.
+        let address = match symbol_addresses.get(name) {
+            Some(address) => *address,
+            None => continue
+        };
+
+        // `address` does not count the entrypoint; in the image the symbol
+        // sits after it.
+        let byte_address = address + entrypoint_len;
+        let word = crate::word_index(address, entrypoint_len);
+        rows.push((byte_address, word, s.size, name.clone(), s.object_file.clone()));
+
+        if name == entry && s.size >= 2 {
+            let halt_address = address + s.size - 2;
+            let halt_word = crate::word_index(halt_address, entrypoint_len);
+            rows.push((byte_address + s.size - 2, halt_word, 2, format!("{}$halt", entry), s.object_file.clone()));
+        }
+    }
+
+    rows.sort_by_key(|r| r.0);
+
+    let mut out = fs::File::create(path)?;
+    for (address, word, size, name, object_file) in rows {
+        writeln!(out, "{:#06x}  word {:<6} size {:<4} {:<24} {}", address, word, size, name, object_file)?;
+    }
+
+    Ok(())
+}
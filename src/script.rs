@@ -0,0 +1,159 @@
+// Minimal linker-script parser, modeled on the `SECTIONS {}` / base-address
+// style of GC decomp linker scripts: a `BASE` address, an `ENTRY` symbol
+// name, and an ordered list of input contributions with optional
+// `ALIGN(n)` directives, e.g.
+//
+//   BASE = 0x1000;
+//   ENTRY(main);
+//   SECTIONS {
+//       start.obj ALIGN(4);
+//       helpers.obj;
+//   }
+
+type Tokens<'a> = std::iter::Peekable<std::slice::Iter<'a, String>>;
+
+#[derive(Debug)]
+pub(crate) struct Contribution {
+    pub(crate) path: String,
+    pub(crate) align: usize,
+}
+
+#[derive(Debug)]
+pub(crate) struct LinkerScript {
+    pub(crate) base: usize,
+    pub(crate) entry: String,
+    pub(crate) contributions: Vec<Contribution>,
+}
+
+fn tokenize(text: &str) -> Vec<String> {
+    let mut spaced = String::new();
+    for c in text.chars() {
+        match c {
+            '{' | '}' | '(' | ')' | ';' | '=' => {
+                spaced.push(' ');
+                spaced.push(c);
+                spaced.push(' ');
+            }
+            _ => spaced.push(c),
+        }
+    }
+    spaced.split_whitespace().map(String::from).collect()
+}
+
+fn parse_number(tok: &str) -> Result<usize, String> {
+    if let Some(hex) = tok.strip_prefix("0x").or_else(|| tok.strip_prefix("0X")) {
+        usize::from_str_radix(hex, 16).map_err(|e| format!("invalid hex literal '{}': {}", tok, e))
+    } else {
+        tok.parse::<usize>().map_err(|e| format!("invalid number '{}': {}", tok, e))
+    }
+}
+
+fn expect(iter: &mut Tokens, want: &str) -> Result<(), String> {
+    match iter.next() {
+        Some(tok) if tok == want => Ok(()),
+        Some(tok) => Err(format!("expected '{}', found '{}'", want, tok)),
+        None => Err(format!("expected '{}', found end of script", want)),
+    }
+}
+
+pub(crate) fn parse(text: &str) -> Result<LinkerScript, String> {
+    let tokens = tokenize(text);
+    let mut iter = tokens.iter().peekable();
+
+    let mut base = 0usize;
+    let mut entry = String::from(crate::ENTRY);
+    let mut contributions = Vec::new();
+
+    while let Some(tok) = iter.next() {
+        match tok.as_str() {
+            "BASE" => {
+                expect(&mut iter, "=")?;
+                let value = iter.next().ok_or_else(|| String::from("expected BASE address"))?;
+                base = parse_number(value)?;
+                expect(&mut iter, ";")?;
+            }
+            "ENTRY" => {
+                expect(&mut iter, "(")?;
+                let name = iter.next().ok_or_else(|| String::from("expected ENTRY symbol name"))?;
+                entry = name.clone();
+                expect(&mut iter, ")")?;
+                expect(&mut iter, ";")?;
+            }
+            "SECTIONS" => {
+                expect(&mut iter, "{")?;
+                loop {
+                    match iter.peek().map(|s| s.as_str()) {
+                        Some("}") => {
+                            iter.next();
+                            break;
+                        }
+                        Some(_) => {
+                            let path = iter.next().unwrap().clone();
+                            let align = match iter.peek().map(|s| s.as_str()) {
+                                Some("ALIGN") => {
+                                    iter.next();
+                                    expect(&mut iter, "(")?;
+                                    let n = iter.next().ok_or_else(|| String::from("expected ALIGN value"))?;
+                                    let n = parse_number(n)?;
+                                    expect(&mut iter, ")")?;
+                                    n
+                                }
+                                _ => 1,
+                            };
+                            expect(&mut iter, ";")?;
+                            contributions.push(Contribution { path, align });
+                        }
+                        None => return Err(String::from("unterminated SECTIONS block")),
+                    }
+                }
+            }
+            other => return Err(format!("unexpected token '{}'", other)),
+        }
+    }
+
+    Ok(LinkerScript { base, entry, contributions })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_base_entry_and_sections() {
+        let script = parse("
+            BASE = 0x1000;
+            ENTRY(start);
+            SECTIONS {
+                start.obj ALIGN(4);
+                helpers.obj;
+            }
+        ").unwrap();
+
+        assert_eq!(script.base, 0x1000);
+        assert_eq!(script.entry, "start");
+        assert_eq!(script.contributions.len(), 2);
+        assert_eq!(script.contributions[0].path, "start.obj");
+        assert_eq!(script.contributions[0].align, 4);
+        assert_eq!(script.contributions[1].path, "helpers.obj");
+        assert_eq!(script.contributions[1].align, 1);
+    }
+
+    #[test]
+    fn defaults_to_base_zero_and_crate_entry_when_omitted() {
+        let script = parse("SECTIONS { only.obj; }").unwrap();
+
+        assert_eq!(script.base, 0);
+        assert_eq!(script.entry, crate::ENTRY);
+        assert_eq!(script.contributions.len(), 1);
+    }
+
+    #[test]
+    fn rejects_unexpected_token() {
+        assert!(parse("FOOBAR;").is_err());
+    }
+
+    #[test]
+    fn rejects_unterminated_sections_block() {
+        assert!(parse("SECTIONS { a.obj;").is_err());
+    }
+}